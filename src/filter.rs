@@ -0,0 +1,245 @@
+//! A public, composable builder for the `find` filter used to tail the oplog: which operation
+//! kinds to include, which namespaces to match, and what `ts` window to scan. [`OplogFilter::build`]
+//! merges all of this into a single BSON `Document`; [`crate::Oplog::new`],
+//! [`crate::Oplog::resume_after`] and [`crate::subscribe_from`] take an `OplogFilter` and call it
+//! themselves, so callers compose a query
+//! instead of hand-crafting BSON and re-implementing namespace matching themselves.
+
+use mongodb::bson::{doc, Document, Timestamp};
+
+/// The oplog operation kinds this crate knows how to convert into an [`crate::Operation`].
+/// [`OplogFilter::build`] always intersects a caller's choice of kinds with this set, so a filter
+/// can never be widened into something `Operation::new`/`from_raw` would reject.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpKind {
+    /// Document inserts (`op: "i"`).
+    Insert,
+    /// Document updates (`op: "u"`).
+    Update,
+    /// Document deletes (`op: "d"`).
+    Delete,
+    /// Commands, including `applyOps` and transactions (`op: "c"`).
+    Command,
+}
+
+impl OpKind {
+    const ALL: [OpKind; 4] = [OpKind::Insert, OpKind::Update, OpKind::Delete, OpKind::Command];
+
+    fn code(self) -> &'static str {
+        match self {
+            OpKind::Insert => "i",
+            OpKind::Update => "u",
+            OpKind::Delete => "d",
+            OpKind::Command => "c",
+        }
+    }
+}
+
+/// How an [`OplogFilter`] matches the `ns` field of oplog entries.
+#[derive(Clone, Debug)]
+enum Namespace {
+    /// Match an exact `database.collection`.
+    Exact(String),
+    /// Match any collection within a database.
+    Database(String),
+    /// Match any `collection` across databases.
+    Collection(String),
+    /// Match `ns` against a raw regular expression.
+    Pattern(String),
+}
+
+/// A public, composable builder for the oplog tailing filter. Restrict which operation kinds are
+/// yielded, which namespaces match, and the `ts` window to scan, then pass it to
+/// [`crate::Oplog::new`] or [`crate::subscribe_from`], or call [`OplogFilter::build`] to get the
+/// underlying `Document`.
+///
+/// ```no_run
+/// use mongodb::bson::Timestamp;
+/// use oplog::{OpKind, OplogFilter};
+///
+/// let filter = OplogFilter::new()
+///     .ops([OpKind::Insert, OpKind::Update])
+///     .database("base")
+///     .since(Timestamp { time: 1, increment: 0 });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OplogFilter {
+    ops: Option<Vec<OpKind>>,
+    namespace: Option<Namespace>,
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+}
+
+impl OplogFilter {
+    /// Start an empty filter, matching every supported operation kind, namespace and time.
+    pub fn new() -> OplogFilter {
+        OplogFilter::default()
+    }
+
+    /// Restrict to the given operation kinds. Defaults to every kind `Operation` understands.
+    pub fn ops(mut self, ops: impl IntoIterator<Item = OpKind>) -> OplogFilter {
+        self.ops = Some(ops.into_iter().collect());
+        self
+    }
+
+    /// Match an exact `database.collection` namespace.
+    pub fn namespace(mut self, database: impl Into<String>, collection: impl Into<String>) -> OplogFilter {
+        self.namespace = Some(Namespace::Exact(format!("{}.{}", database.into(), collection.into())));
+        self
+    }
+
+    /// Match any collection within `database`.
+    pub fn database(mut self, database: impl Into<String>) -> OplogFilter {
+        self.namespace = Some(Namespace::Database(database.into()));
+        self
+    }
+
+    /// Match `collection` across any database, e.g. to tail a sharded or multi-tenant layout
+    /// where the same collection name recurs under several databases.
+    pub fn collection(mut self, collection: impl Into<String>) -> OplogFilter {
+        self.namespace = Some(Namespace::Collection(collection.into()));
+        self
+    }
+
+    /// Match `ns` against a raw regular expression, for namespace layouts `database`/`collection`
+    /// can't express.
+    pub fn namespace_pattern(mut self, pattern: impl Into<String>) -> OplogFilter {
+        self.namespace = Some(Namespace::Pattern(pattern.into()));
+        self
+    }
+
+    /// Restrict to entries at or after `ts`.
+    pub fn since(mut self, ts: Timestamp) -> OplogFilter {
+        self.since = Some(ts);
+        self
+    }
+
+    /// Restrict to entries at or before `ts`.
+    pub fn until(mut self, ts: Timestamp) -> OplogFilter {
+        self.until = Some(ts);
+        self
+    }
+
+    /// Merge this filter into the `Document` passed to `find` against `local.oplog.rs`, always
+    /// intersected with the operation kinds this crate can convert.
+    pub fn build(&self) -> Document {
+        let ops: Vec<&str> = match &self.ops {
+            Some(ops) => ops.iter().map(|op| op.code()).collect(),
+            None => OpKind::ALL.iter().map(|op| op.code()).collect(),
+        };
+
+        let mut filter = doc! { "op": { "$in": ops } };
+
+        match &self.namespace {
+            Some(Namespace::Exact(ns)) => {
+                filter.insert("ns", ns.clone());
+            }
+            Some(Namespace::Database(db)) => {
+                filter.insert("ns", doc! { "$regex": format!("^{}\\.", escape(db)) });
+            }
+            Some(Namespace::Collection(coll)) => {
+                filter.insert("ns", doc! { "$regex": format!("\\.{}$", escape(coll)) });
+            }
+            Some(Namespace::Pattern(pattern)) => {
+                filter.insert("ns", doc! { "$regex": pattern.clone() });
+            }
+            None => {}
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let mut ts = Document::new();
+            if let Some(since) = self.since {
+                ts.insert("$gte", since);
+            }
+            if let Some(until) = self.until {
+                ts.insert("$lte", until);
+            }
+            filter.insert("ts", ts);
+        }
+
+        filter
+    }
+}
+
+/// Escape the regex metacharacters MongoDB's `$regex` operator understands, so a literal
+/// database or collection name passed to [`OplogFilter::database`]/[`OplogFilter::collection`]
+/// can't be misread as a pattern.
+fn escape(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OpKind, OplogFilter};
+    use mongodb::bson::{doc, Timestamp};
+
+    #[test]
+    fn default_filter_matches_every_supported_op() {
+        let filter = OplogFilter::new().build();
+
+        assert_eq!(filter, doc! { "op": { "$in": ["i", "u", "d", "c"] } });
+    }
+
+    #[test]
+    fn ops_restricts_to_the_given_kinds() {
+        let filter = OplogFilter::new().ops([OpKind::Insert, OpKind::Delete]).build();
+
+        assert_eq!(filter, doc! { "op": { "$in": ["i", "d"] } });
+    }
+
+    #[test]
+    fn namespace_matches_an_exact_collection() {
+        let filter = OplogFilter::new().namespace("base", "gps_latest").build();
+
+        assert_eq!(
+            filter,
+            doc! { "op": { "$in": ["i", "u", "d", "c"] }, "ns": "base.gps_latest" }
+        );
+    }
+
+    #[test]
+    fn database_matches_any_collection_within_it() {
+        let filter = OplogFilter::new().database("base").build();
+
+        assert_eq!(
+            filter,
+            doc! { "op": { "$in": ["i", "u", "d", "c"] }, "ns": { "$regex": "^base\\." } }
+        );
+    }
+
+    #[test]
+    fn collection_matches_across_databases() {
+        let filter = OplogFilter::new().collection("gps_latest").build();
+
+        assert_eq!(
+            filter,
+            doc! { "op": { "$in": ["i", "u", "d", "c"] }, "ns": { "$regex": "\\.gps_latest$" } }
+        );
+    }
+
+    #[test]
+    fn since_and_until_bound_the_ts_window() {
+        let filter = OplogFilter::new()
+            .since(Timestamp { time: 1, increment: 0 })
+            .until(Timestamp { time: 2, increment: 0 })
+            .build();
+
+        assert_eq!(
+            filter,
+            doc! {
+                "op": { "$in": ["i", "u", "d", "c"] },
+                "ts": {
+                    "$gte": Timestamp { time: 1, increment: 0 },
+                    "$lte": Timestamp { time: 2, increment: 0 },
+                }
+            }
+        );
+    }
+}
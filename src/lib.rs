@@ -1,23 +1,28 @@
 // #![warn(missing_docs)]
 #![feature(async_iterator)]
 
+use std::collections::HashMap;
 use std::fmt;
 use std::result;
 
 use futures::pin_mut;
 use futures::StreamExt;
-use mongodb::bson::doc;
 use mongodb::bson::document::ValueAccessError;
-use mongodb::bson::Document;
+use mongodb::bson::{Bson, Document, Timestamp};
 use mongodb::Client;
-pub(crate) use operation::Operation;
-pub(crate) use oplog::Oplog;
+pub use operation::Operation;
+pub use oplog::Oplog;
 use serde::de::DeserializeOwned;
 use tokio::sync::mpsc::Receiver;
 use tokio_context::context::Context;
+pub use filter::{OpKind, OplogFilter};
+pub use oplog::{Backoff, OplogBuilder};
+pub use update::{Diff, UpdateSpec};
 
+mod filter;
 mod operation;
 mod oplog;
+mod update;
 
 /// A type alias for convenience so we can fix the error to our own `Error` type.
 pub type Result<T> = result::Result<T, Error>;
@@ -56,29 +61,45 @@ impl From<mongodb::error::Error> for Error {
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub enum Event<T> {
-    Added(T),
-    Updated(T),
-    Deleted(T),
+    /// A document was inserted, along with the oplog position it was observed at.
+    Added(T, Timestamp),
+    /// A document was updated, along with the oplog position it was observed at.
+    Updated(T, Timestamp),
+    /// A document was deleted, along with the oplog position it was observed at.
+    Deleted(T, Timestamp),
+    /// An error converting an oplog entry into `T`, or some other failure while tailing.
     Error(String),
 }
 
+impl<T> Event<T> {
+    /// Returns the oplog position this event was observed at, or `None` for `Event::Error`.
+    /// Consumers can persist this as a checkpoint and pass it to [`subscribe_from`] on reconnect
+    /// to resume tailing without replaying or missing entries.
+    pub fn checkpoint(&self) -> Option<Timestamp> {
+        match *self {
+            Event::Added(_, ts) | Event::Updated(_, ts) | Event::Deleted(_, ts) => Some(ts),
+            Event::Error(_) => None,
+        }
+    }
+}
+
 impl<T> std::fmt::Display for Event<T>
 where
     T: std::fmt::Debug + serde::Serialize,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Event::Added(ref t) => write!(
+            Event::Added(ref t, _) => write!(
                 f,
                 r#"{{ \"type\": \"ADDED\", \"object\": {:?} }}"#,
                 serde_json::to_string(&t).unwrap()
             ),
-            Event::Updated(t) => write!(
+            Event::Updated(t, _) => write!(
                 f,
                 r#"{{ \"type\": \"MODIFIED\", \"object\": {:?} }}"#,
                 serde_json::to_string(&t).unwrap()
             ),
-            Event::Deleted(t) => write!(
+            Event::Deleted(t, _) => write!(
                 f,
                 r#"{{ \"type\": \"DELETED\", \"object\": {:?} }}"#,
                 serde_json::to_string(&t).unwrap()
@@ -88,57 +109,123 @@ where
     }
 }
 
+/// A key identifying a document's pre-image in the cache `subscribe_from` keeps so an update's
+/// `o` (which is only a diff, not a full document) can be turned into a full `T`.
+fn cache_key(id: &Bson) -> String {
+    format!("{:?}", id)
+}
+
 pub fn subscribe<'a, T>(
     ctx: Context,
     client: Client,
     ns: &str,
     coll: &str,
-    filter: Option<Document>,
+    filter: Option<OplogFilter>,
 ) -> Result<Receiver<Event<T>>>
 where
     T: core::fmt::Debug + DeserializeOwned + Send + Sync + 'static,
 {
-    let ns = format!("{}.{}", ns, coll);
-    let filter = if let Some(mut filter) = filter {
-        filter.insert("ns", ns);
-        filter
-    } else {
-        doc! {"ns":ns}
-    };
+    subscribe_from(ctx, client, ns, coll, filter, None)
+}
+
+/// Like [`subscribe`], but resumes tailing strictly after `resume_after` instead of scanning from
+/// the start of the oplog. Pass the `Timestamp` from a previously persisted
+/// [`Event::checkpoint`] to avoid replaying or missing entries across a restart.
+pub fn subscribe_from<'a, T>(
+    ctx: Context,
+    client: Client,
+    ns: &str,
+    coll: &str,
+    filter: Option<OplogFilter>,
+    resume_after: Option<Timestamp>,
+) -> Result<Receiver<Event<T>>>
+where
+    T: core::fmt::Debug + DeserializeOwned + Send + Sync + 'static,
+{
+    // `ns`/`coll` pin the exact namespace this typed subscription deserializes into, overriding
+    // whatever namespace match the caller's filter set.
+    let filter = filter.unwrap_or_default().namespace(ns, coll);
     let (tx, rx) = tokio::sync::mpsc::channel(4);
     tokio::spawn(async move {
         let block = async move {
-            let mut oplog = Oplog::new(&client, filter).await.unwrap();
+            let oplog = match resume_after {
+                Some(ts) => Oplog::resume_after(client, filter, ts).await,
+                None => Oplog::new(client, filter).await,
+            };
+            let mut oplog = match oplog {
+                Ok(oplog) => oplog,
+                Err(e) => {
+                    let _ = tx.send(Event::Error(e.to_string())).await;
+                    return;
+                }
+            };
 
             let stream = oplog.stream();
             pin_mut!(stream);
 
+            // The pre-image of every document currently known, keyed by `_id`, so an update's
+            // `o` (just the `$set`/`$unset` modifiers or a `$v:2` diff, never a full document)
+            // can be turned into a full `T` by applying it on top of the last observed image.
+            let mut documents: HashMap<String, Document> = HashMap::new();
+
             while let Some(op) = stream.next().await {
+                let op = match op {
+                    Ok(op) => op,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Event::Error(e.to_string()))
+                            .await
+                            .map_err(|e| log::error!("Error sending event: {}", e));
+                        continue;
+                    }
+                };
+
+                let ts = op.ts();
                 let evt = match op {
                     Operation::Insert { document, .. } => {
+                        if let Some(id) = document.get("_id") {
+                            documents.insert(cache_key(id), document.clone());
+                        }
                         match mongodb::bson::from_document::<T>(document) {
-                            Ok(t) => Event::Added(t),
-                            Err(e) => Event::Error(e.to_string()),
+                            Ok(t) => Some(Event::Added(t, ts)),
+                            Err(e) => Some(Event::Error(e.to_string())),
                         }
                     }
-                    Operation::Update { document, .. } => {
-                        match mongodb::bson::from_document::<T>(document) {
-                            Ok(t) => Event::Updated(t),
-                            Err(e) => Event::Error(e.to_string()),
+                    Operation::Update { query, update, .. } => {
+                        let id = query.get("_id").cloned();
+                        let mut image = id
+                            .as_ref()
+                            .and_then(|id| documents.get(&cache_key(id)).cloned())
+                            .unwrap_or(query);
+                        update.apply(&mut image);
+                        if let Some(id) = &id {
+                            documents.insert(cache_key(id), image.clone());
+                        }
+                        match mongodb::bson::from_document::<T>(image) {
+                            Ok(t) => Some(Event::Updated(t, ts)),
+                            Err(e) => Some(Event::Error(e.to_string())),
                         }
                     }
                     Operation::Delete { document, .. } => {
+                        if let Some(id) = document.get("_id") {
+                            documents.remove(&cache_key(id));
+                        }
                         match mongodb::bson::from_document::<T>(document) {
-                            Ok(t) => Event::Deleted(t),
-                            Err(e) => Event::Error(e.to_string()),
+                            Ok(t) => Some(Event::Deleted(t, ts)),
+                            Err(e) => Some(Event::Error(e.to_string())),
                         }
                     }
+                    // Command and applyOps entries don't map onto a single `T`; callers that
+                    // need their contents should use the lower-level `Oplog` API directly.
+                    Operation::ApplyOps { .. } | Operation::Command { .. } => None,
                 };
 
-                let _ = tx
-                    .send(evt)
-                    .await
-                    .map_err(|e| log::error!("Error sending event: {}", e));
+                if let Some(evt) = evt {
+                    let _ = tx
+                        .send(evt)
+                        .await
+                        .map_err(|e| log::error!("Error sending event: {}", e));
+                }
             }
         };
 
@@ -4,12 +4,16 @@
 //! datetimes).
 //!
 //! As we accept _any_ document, it may not be a valid operation so wrap any conversions in a
-//! `Result`.
+//! `Result`. [`Operation::new`] builds from an owned `Document`; [`Operation::from_raw`] builds
+//! from a borrowed `RawDocument` without materializing fields the caller doesn't need.
 
+use std::convert::TryFrom;
 use std::fmt;
 
+use crate::update::UpdateSpec;
 use crate::Result;
-use mongodb::bson::{Bson, DateTime, Document};
+use mongodb::bson::raw::{RawBsonRef, RawDocument};
+use mongodb::bson::{document::ValueAccessError, Bson, DateTime, Document, Timestamp};
 
 /// A MongoDB oplog operation.
 #[derive(Clone, Debug, PartialEq, serde::Deserialize)]
@@ -18,6 +22,8 @@ pub enum Operation {
     Insert {
         /// The time of the operation.
         timestamp: DateTime,
+        /// The raw oplog clock of the operation, usable as a resume cursor position.
+        ts: Timestamp,
         /// The full namespace of the operation including its database and collection.
         namespace: String,
         /// The BSON document inserted into the namespace.
@@ -27,22 +33,63 @@ pub enum Operation {
     Update {
         /// The time of the operation.
         timestamp: DateTime,
+        /// The raw oplog clock of the operation, usable as a resume cursor position.
+        ts: Timestamp,
         /// The full namespace of the operation including its database and collection.
         namespace: String,
-        // /// The BSON selection criteria for the update.
-        // query: Document,
-        /// The BSON update applied in this operation.
+        /// The BSON selection criteria for the update (its `o2`), typically just the `_id`.
+        query: Document,
+        /// The raw BSON update document (`o`) as written to the oplog, either `$v:1` modifiers
+        /// or a `$v:2` diff.
         document: Document,
+        /// The same update, decoded into a structured form. Use `update.apply(&mut pre_image)`
+        /// to reconstruct the post-image document.
+        update: UpdateSpec,
     },
     /// The deletion of a document in a specific database and collection matching a given query.
     Delete {
         /// The time of the operation.
         timestamp: DateTime,
+        /// The raw oplog clock of the operation, usable as a resume cursor position.
+        ts: Timestamp,
         /// The full namespace of the operation including its database and collection.
         namespace: String,
         /// The BSON selection criteria for the delete.
         document: Document,
     },
+    /// A flattened `applyOps` command, grouping the sub-operations that it applied atomically.
+    /// MongoDB uses this both for the explicit `applyOps` command and for multi-document
+    /// transactions, which may be split across several linked `applyOps` entries.
+    ApplyOps {
+        /// The time of the operation.
+        timestamp: DateTime,
+        /// The raw oplog clock of the operation, usable as a resume cursor position.
+        ts: Timestamp,
+        /// The full namespace the `applyOps` command was run against.
+        namespace: String,
+        /// The sub-operations applied atomically, in order.
+        ops: Vec<Operation>,
+        /// The session id of the transaction this `applyOps` belongs to, if any.
+        lsid: Option<Document>,
+        /// The transaction number within `lsid`, if this `applyOps` is part of a transaction.
+        txn_number: Option<i64>,
+        /// Whether this is one entry of a multi-entry transaction that has not been fully
+        /// applied yet (`partialTxn`). Consumers linking entries together via `lsid` and
+        /// `txn_number` should buffer partial entries until one arrives with this unset.
+        partial_txn: bool,
+    },
+    /// A command other than `applyOps`, such as `create`, `drop` or `dropDatabase`, kept as the
+    /// raw command document since there is no single shape to normalize it into.
+    Command {
+        /// The time of the operation.
+        timestamp: DateTime,
+        /// The raw oplog clock of the operation, usable as a resume cursor position.
+        ts: Timestamp,
+        /// The full namespace the command was run against.
+        namespace: String,
+        /// The raw command document.
+        document: Document,
+    },
 }
 
 impl Operation {
@@ -55,10 +102,145 @@ impl Operation {
             "i" => Operation::from_insert(document),
             "u" => Operation::from_update(document),
             "d" => Operation::from_delete(document),
+            "c" => Operation::from_command(document),
+            op => Err(crate::Error::UnknownOperation(op.into())),
+        }
+    }
+
+    /// Raw-bytes counterpart to [`Operation::new`]: scans `raw` for `op`, `ns` and `ts` without
+    /// parsing the rest of the entry, and only materializes the `o`/`o2` sub-document (the part
+    /// a consumer actually needs) into an owned [`Document`] once the operation kind is known,
+    /// rather than letting the driver decode every field of every entry up front. The resulting
+    /// `Operation` still carries that sub-document as an owned [`Document`]; callers deserializing
+    /// into a typed `T` (e.g. via [`crate::subscribe`]) convert from there with serde as usual,
+    /// this only avoids parsing the fields `Operation` itself has no use for.
+    /// Meant for tailing a busy oplog, where most entries' fields are never inspected.
+    pub fn from_raw(raw: &RawDocument) -> Result<Operation> {
+        let op = match raw.get("op") {
+            Ok(Some(RawBsonRef::String(op))) => op,
+            _ => return Err(crate::Error::MissingField(ValueAccessError::NotPresent)),
+        };
+
+        match op {
+            "i" => Operation::from_raw_insert(raw),
+            "u" => Operation::from_raw_update(raw),
+            "d" => Operation::from_raw_delete(raw),
+            "c" => Operation::from_raw_command(raw),
             op => Err(crate::Error::UnknownOperation(op.into())),
         }
     }
 
+    fn raw_ts(raw: &RawDocument) -> Result<Timestamp> {
+        raw.get_timestamp("ts")
+            .map_err(|_| crate::Error::MissingField(ValueAccessError::NotPresent))
+    }
+
+    fn raw_ns(raw: &RawDocument) -> Result<&str> {
+        raw.get_str("ns")
+            .map_err(|_| crate::Error::MissingField(ValueAccessError::NotPresent))
+    }
+
+    /// Materialize the sub-document at `key` (typically `"o"` or `"o2"`) into an owned
+    /// `Document`; this is the only sub-document of the entry that `from_raw` copies out of `raw`.
+    fn raw_sub_document(raw: &RawDocument, key: &str) -> Result<Document> {
+        let sub = raw
+            .get_document(key)
+            .map_err(|_| crate::Error::MissingField(ValueAccessError::NotPresent))?;
+
+        Document::try_from(sub).map_err(|_| crate::Error::InvalidOperation)
+    }
+
+    fn from_raw_insert(raw: &RawDocument) -> Result<Operation> {
+        let ts = Operation::raw_ts(raw)?;
+        let ns = Operation::raw_ns(raw)?;
+        let o = Operation::raw_sub_document(raw, "o")?;
+
+        Ok(Operation::Insert {
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
+            namespace: ns.into(),
+            document: o,
+        })
+    }
+
+    fn from_raw_update(raw: &RawDocument) -> Result<Operation> {
+        let ts = Operation::raw_ts(raw)?;
+        let ns = Operation::raw_ns(raw)?;
+        let o2 = Operation::raw_sub_document(raw, "o2")?;
+        let o = Operation::raw_sub_document(raw, "o")?;
+
+        Ok(Operation::Update {
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
+            namespace: ns.into(),
+            query: o2,
+            update: UpdateSpec::parse(&o),
+            document: o,
+        })
+    }
+
+    fn from_raw_delete(raw: &RawDocument) -> Result<Operation> {
+        let ts = Operation::raw_ts(raw)?;
+        let ns = Operation::raw_ns(raw)?;
+        let o = Operation::raw_sub_document(raw, "o")?;
+
+        Ok(Operation::Delete {
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
+            namespace: ns.into(),
+            document: o,
+        })
+    }
+
+    /// Mirrors [`Operation::build_command`], but sources `lsid`/`txnNumber`/`partialTxn` from
+    /// `raw` directly instead of from an already-owned `Document`, since only `o` is materialized
+    /// and the rest of the entry is never converted into a `Document`.
+    fn from_raw_command(raw: &RawDocument) -> Result<Operation> {
+        let ts = Operation::raw_ts(raw)?;
+        let ns = Operation::raw_ns(raw)?;
+        let o = Operation::raw_sub_document(raw, "o")?;
+
+        if let Ok(apply_ops) = o.get_array("applyOps") {
+            let mut ops = Vec::with_capacity(apply_ops.len());
+            for entry in apply_ops {
+                let entry = entry.as_document().ok_or(crate::Error::InvalidOperation)?;
+                ops.push(Operation::from_apply_ops_entry(entry, ts)?);
+            }
+
+            return Ok(Operation::ApplyOps {
+                timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+                ts,
+                namespace: ns.into(),
+                ops,
+                lsid: raw
+                    .get_document("lsid")
+                    .ok()
+                    .and_then(|d| Document::try_from(d).ok()),
+                txn_number: raw.get_i64("txnNumber").ok(),
+                partial_txn: raw.get_bool("partialTxn").unwrap_or(false),
+            });
+        }
+
+        Ok(Operation::Command {
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
+            namespace: ns.into(),
+            document: o,
+        })
+    }
+
+    /// Returns the raw oplog clock of this operation, suitable for persisting as a checkpoint
+    /// and passing to [`crate::oplog::OplogBuilder::starting_at`] to resume tailing after it.
+    pub fn ts(&self) -> Timestamp {
+        match *self {
+            Operation::Insert { ts, .. }
+            | Operation::Update { ts, .. }
+            | Operation::Delete { ts, .. }
+            | Operation::ApplyOps { ts, .. }
+            | Operation::Command { ts, .. } => ts,
+        }
+    }
+
     /// Returns an operation from any BSON value.
     fn from_bson(bson: &Bson) -> Result<Operation> {
         match *bson {
@@ -72,6 +254,13 @@ impl Operation {
         let ts = document
             .get_timestamp("ts")
             .map_err(|e| crate::Error::MissingField(e))?;
+
+        Operation::build_insert(document, ts)
+    }
+
+    /// Build an insert operation for `document`, using `ts` as its timestamp. Used both for
+    /// top-level `"i"` entries and for `applyOps` sub-entries, which inherit their wrapper's `ts`.
+    fn build_insert(document: &Document, ts: Timestamp) -> Result<Operation> {
         let ns = document
             .get_str("ns")
             .map_err(|e| crate::Error::MissingField(e))?;
@@ -80,7 +269,8 @@ impl Operation {
             .map_err(|e| crate::Error::MissingField(e))?;
 
         Ok(Operation::Insert {
-            timestamp: DateTime::from_millis((ts.time + ts.increment) as i64),
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
             namespace: ns.into(),
             document: o.to_owned(),
         })
@@ -91,16 +281,29 @@ impl Operation {
         let ts = document
             .get_timestamp("ts")
             .map_err(|e| crate::Error::MissingField(e))?;
+
+        Operation::build_update(document, ts)
+    }
+
+    /// Build an update operation for `document`, using `ts` as its timestamp. Used both for
+    /// top-level `"u"` entries and for `applyOps` sub-entries, which inherit their wrapper's `ts`.
+    fn build_update(document: &Document, ts: Timestamp) -> Result<Operation> {
         let ns = document
             .get_str("ns")
             .map_err(|e| crate::Error::MissingField(e))?;
+        let o2 = document
+            .get_document("o2")
+            .map_err(|e| crate::Error::MissingField(e))?;
         let o = document
             .get_document("o")
             .map_err(|e| crate::Error::MissingField(e))?;
 
         Ok(Operation::Update {
-            timestamp: DateTime::from_millis((ts.time + ts.increment) as i64),
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
             namespace: ns.into(),
+            query: o2.to_owned(),
+            update: UpdateSpec::parse(o),
             document: o.to_owned(),
         })
     }
@@ -110,6 +313,13 @@ impl Operation {
         let ts = document
             .get_timestamp("ts")
             .map_err(|e| crate::Error::MissingField(e))?;
+
+        Operation::build_delete(document, ts)
+    }
+
+    /// Build a delete operation for `document`, using `ts` as its timestamp. Used both for
+    /// top-level `"d"` entries and for `applyOps` sub-entries, which inherit their wrapper's `ts`.
+    fn build_delete(document: &Document, ts: Timestamp) -> Result<Operation> {
         let ns = document
             .get_str("ns")
             .map_err(|e| crate::Error::MissingField(e))?;
@@ -118,11 +328,75 @@ impl Operation {
             .map_err(|e| crate::Error::MissingField(e))?;
 
         Ok(Operation::Delete {
-            timestamp: DateTime::from_millis((ts.time + ts.increment) as i64),
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
             namespace: ns.into(),
             document: o.to_owned(),
         })
     }
+
+    /// Return a command operation for a given document, flattening it if it is an `applyOps`.
+    fn from_command(document: &Document) -> Result<Operation> {
+        let ts = document
+            .get_timestamp("ts")
+            .map_err(|e| crate::Error::MissingField(e))?;
+
+        Operation::build_command(document, ts)
+    }
+
+    /// Build a command operation for `document`, using `ts` as its timestamp. If `document`'s
+    /// `"o"` holds an `applyOps` array, recursively convert each sub-document (which may itself
+    /// be a nested `applyOps`) into an `Operation`, inheriting `ts` since sub-entries carry no
+    /// `ts` of their own. Otherwise, this is a generic command (e.g. `create`, `drop`).
+    fn build_command(document: &Document, ts: Timestamp) -> Result<Operation> {
+        let ns = document
+            .get_str("ns")
+            .map_err(|e| crate::Error::MissingField(e))?;
+        let o = document
+            .get_document("o")
+            .map_err(|e| crate::Error::MissingField(e))?;
+
+        if let Ok(apply_ops) = o.get_array("applyOps") {
+            let mut ops = Vec::with_capacity(apply_ops.len());
+            for entry in apply_ops {
+                let entry = entry.as_document().ok_or(crate::Error::InvalidOperation)?;
+                ops.push(Operation::from_apply_ops_entry(entry, ts)?);
+            }
+
+            return Ok(Operation::ApplyOps {
+                timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+                ts,
+                namespace: ns.into(),
+                ops,
+                lsid: document.get_document("lsid").ok().cloned(),
+                txn_number: document.get_i64("txnNumber").ok(),
+                partial_txn: document.get_bool("partialTxn").unwrap_or(false),
+            });
+        }
+
+        Ok(Operation::Command {
+            timestamp: DateTime::from_millis(ts.time as i64 * 1000),
+            ts,
+            namespace: ns.into(),
+            document: o.to_owned(),
+        })
+    }
+
+    /// Convert one entry of an `applyOps` array, which is a mini-oplog document with its own
+    /// `op`/`ns`/`o` but no `ts`, so the wrapper's `ts` is used instead.
+    fn from_apply_ops_entry(entry: &Document, ts: Timestamp) -> Result<Operation> {
+        let op = entry
+            .get_str("op")
+            .map_err(|e| crate::Error::MissingField(e))?;
+
+        match op {
+            "i" => Operation::build_insert(entry, ts),
+            "u" => Operation::build_update(entry, ts),
+            "d" => Operation::build_delete(entry, ts),
+            "c" => Operation::build_command(entry, ts),
+            op => Err(crate::Error::UnknownOperation(op.into())),
+        }
+    }
 }
 
 impl fmt::Display for Operation {
@@ -132,6 +406,7 @@ impl fmt::Display for Operation {
                 timestamp,
                 ref namespace,
                 ref document,
+                ..
             } => {
                 write!(
                     f,
@@ -142,21 +417,46 @@ impl fmt::Display for Operation {
             Operation::Update {
                 timestamp,
                 ref namespace,
+                ref query,
                 ref document,
+                ..
             } => {
                 write!(
                     f,
-                    "Update #{} at {}: {}",
-                    namespace, timestamp, document
+                    "Update #{} matching {} at {}: {}",
+                    namespace, query, timestamp, document
                 )
             }
             Operation::Delete {
                 timestamp,
                 ref namespace,
                 ref document,
+                ..
             } => {
                 write!(f, "Delete # from {} at {}: {}", namespace, timestamp, document)
             }
+            Operation::ApplyOps {
+                timestamp,
+                ref namespace,
+                ref ops,
+                ..
+            } => {
+                write!(
+                    f,
+                    "ApplyOps # on {} at {}: {} op(s)",
+                    namespace,
+                    timestamp,
+                    ops.len()
+                )
+            }
+            Operation::Command {
+                timestamp,
+                ref namespace,
+                ref document,
+                ..
+            } => {
+                write!(f, "Command # on {} at {}: {}", namespace, timestamp, document)
+            }
         }
     }
 }
@@ -182,7 +482,8 @@ mod tests {
         assert_eq!(
             operation,
             Operation::Insert {
-                timestamp: DateTime::from_millis(1479419534),
+                timestamp: DateTime::from_millis(1479419534000),
+                ts: Timestamp { time: 1479419534, increment: 0 },
                 namespace: "foo.bar".into(),
                 document: doc! { "foo" : "bar" },
             }
@@ -199,8 +500,8 @@ mod tests {
                 "_id": 1
             },
             "o": {
-                "data": {
-                    "foo": "baz"
+                "$set": {
+                    "data.foo": "baz"
                 }
             }
         };
@@ -209,13 +510,54 @@ mod tests {
         assert_eq!(
             operation,
             Operation::Update {
-                timestamp: DateTime::from_millis(1479561033),
+                timestamp: DateTime::from_millis(1479561033000),
+                ts: Timestamp { time: 1479561033, increment: 0 },
                 namespace: "foo.bar".into(),
-                document: doc! { "data": { "foo": "baz" } },
+                query: doc! { "_id": 1 },
+                document: doc! { "$set": { "data.foo": "baz" } },
+                update: crate::update::UpdateSpec::Modifiers {
+                    set: doc! { "data.foo": "baz" },
+                    unset: vec![],
+                },
             }
         );
     }
 
+    #[test]
+    fn operation_converts_v2_diff_updates() {
+        let doc = doc! {
+            "ts": Timestamp{time:1479561033,increment:0},
+            "op": "u",
+            "ns": "foo.bar",
+            "o2": {
+                "_id": 1
+            },
+            "o": {
+                "$v": 2,
+                "diff": {
+                    "u": { "foo": "baz" }
+                }
+            }
+        };
+        let operation = Operation::new(&doc).unwrap();
+
+        match operation {
+            Operation::Update { query, update, .. } => {
+                assert_eq!(query, doc! { "_id": 1 });
+                assert_eq!(
+                    update,
+                    crate::update::UpdateSpec::Diff(crate::update::Diff {
+                        set: doc! { "foo": "baz" },
+                        unset: vec![],
+                        insert: doc! {},
+                        sub_diffs: Default::default(),
+                    })
+                );
+            }
+            _ => panic!("Expected Update operation."),
+        }
+    }
+
     #[test]
     fn operation_converts_deletes() {
         let doc = doc! {
@@ -231,13 +573,141 @@ mod tests {
         assert_eq!(
             operation,
             Operation::Delete {
-                timestamp: DateTime::from_millis(1661330782),
+                timestamp: DateTime::from_millis(1661330782000),
+                ts: Timestamp { time: 1661330782, increment: 0 },
                 namespace: "foo.bar".into(),
                 document: doc! { "_id": 1 },
             }
         );
     }
 
+    #[test]
+    fn operation_converts_apply_ops() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479419534, increment: 1},
+            "op": "c",
+            "ns": "admin.$cmd",
+            "o": {
+                "applyOps": [
+                    {
+                        "op": "i",
+                        "ns": "foo.bar",
+                        "o": { "_id": 1, "foo": "bar" }
+                    },
+                    {
+                        "op": "d",
+                        "ns": "foo.bar",
+                        "o": { "_id": 2 }
+                    }
+                ]
+            }
+        };
+        let operation = Operation::new(&doc).unwrap();
+
+        assert_eq!(
+            operation,
+            Operation::ApplyOps {
+                timestamp: DateTime::from_millis(1479419534000),
+                ts: Timestamp { time: 1479419534, increment: 1 },
+                namespace: "admin.$cmd".into(),
+                ops: vec![
+                    Operation::Insert {
+                        timestamp: DateTime::from_millis(1479419534000),
+                        ts: Timestamp { time: 1479419534, increment: 1 },
+                        namespace: "foo.bar".into(),
+                        document: doc! { "_id": 1, "foo": "bar" },
+                    },
+                    Operation::Delete {
+                        timestamp: DateTime::from_millis(1479419534000),
+                        ts: Timestamp { time: 1479419534, increment: 1 },
+                        namespace: "foo.bar".into(),
+                        document: doc! { "_id": 2 },
+                    },
+                ],
+                lsid: None,
+                txn_number: None,
+                partial_txn: false,
+            }
+        );
+    }
+
+    #[test]
+    fn operation_converts_empty_apply_ops() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479419534, increment: 0},
+            "op": "c",
+            "ns": "admin.$cmd",
+            "o": { "applyOps": [] }
+        };
+        let operation = Operation::new(&doc).unwrap();
+
+        assert_eq!(
+            operation,
+            Operation::ApplyOps {
+                timestamp: DateTime::from_millis(1479419534000),
+                ts: Timestamp { time: 1479419534, increment: 0 },
+                namespace: "admin.$cmd".into(),
+                ops: vec![],
+                lsid: None,
+                txn_number: None,
+                partial_txn: false,
+            }
+        );
+    }
+
+    #[test]
+    fn operation_converts_transaction_apply_ops() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479419534, increment: 0},
+            "op": "c",
+            "ns": "admin.$cmd",
+            "lsid": { "id": 1 },
+            "txnNumber": 7i64,
+            "partialTxn": true,
+            "o": {
+                "applyOps": [
+                    { "op": "i", "ns": "foo.bar", "o": { "_id": 1 } }
+                ]
+            }
+        };
+        let operation = Operation::new(&doc).unwrap();
+
+        match operation {
+            Operation::ApplyOps {
+                lsid,
+                txn_number,
+                partial_txn,
+                ..
+            } => {
+                assert_eq!(lsid, Some(doc! { "id": 1 }));
+                assert_eq!(txn_number, Some(7));
+                assert!(partial_txn);
+            }
+            _ => panic!("Expected ApplyOps operation."),
+        }
+    }
+
+    #[test]
+    fn operation_converts_generic_commands() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479419534, increment: 0},
+            "op": "c",
+            "ns": "foo.$cmd",
+            "o": { "create": "bar" }
+        };
+        let operation = Operation::new(&doc).unwrap();
+
+        assert_eq!(
+            operation,
+            Operation::Command {
+                timestamp: DateTime::from_millis(1479419534000),
+                ts: Timestamp { time: 1479419534, increment: 0 },
+                namespace: "foo.$cmd".into(),
+                document: doc! { "create": "bar" },
+            }
+        );
+    }
+
     #[test]
     fn operation_returns_unknown_operations() {
         let doc = doc! { "op": "x" };
@@ -259,4 +729,55 @@ mod tests {
             _ => panic!("Expected missing field."),
         }
     }
+
+    #[test]
+    fn operation_ts_returns_the_raw_oplog_clock() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479419534, increment: 3},
+            "op": "i",
+            "ns": "foo.bar",
+            "o": { "foo": "bar" }
+        };
+        let operation = Operation::new(&doc).unwrap();
+
+        assert_eq!(operation.ts(), Timestamp { time: 1479419534, increment: 3 });
+    }
+
+    #[test]
+    fn operation_from_raw_matches_operation_new() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479419534, increment: 0},
+            "op": "i",
+            "ns": "foo.bar",
+            "o": { "foo": "bar" }
+        };
+        let raw = mongodb::bson::RawDocumentBuf::from_document(&doc).unwrap();
+
+        assert_eq!(Operation::from_raw(&raw).unwrap(), Operation::new(&doc).unwrap());
+    }
+
+    #[test]
+    fn operation_from_raw_converts_updates() {
+        let doc = doc! {
+            "ts": Timestamp{time: 1479561033, increment: 0},
+            "op": "u",
+            "ns": "foo.bar",
+            "o2": { "_id": 1 },
+            "o": { "$set": { "foo": "baz" } }
+        };
+        let raw = mongodb::bson::RawDocumentBuf::from_document(&doc).unwrap();
+
+        assert_eq!(Operation::from_raw(&raw).unwrap(), Operation::new(&doc).unwrap());
+    }
+
+    #[test]
+    fn operation_from_raw_returns_unknown_operations() {
+        let doc = doc! { "op": "x" };
+        let raw = mongodb::bson::RawDocumentBuf::from_document(&doc).unwrap();
+
+        match Operation::from_raw(&raw) {
+            Err(Error::UnknownOperation(op)) => assert_eq!(op, "x"),
+            _ => panic!("Expected unknown operation."),
+        }
+    }
 }
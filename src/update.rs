@@ -0,0 +1,326 @@
+//! Decoding of MongoDB oplog update (`o`) documents into a structured form capable of turning a
+//! pre-image into a post-image.
+//!
+//! MongoDB has used two different encodings for an update's `o` document over time: the legacy
+//! `$v:1` format expresses the change as `$set`/`$unset` modifiers, while the `$v:2` format
+//! introduced in MongoDB 5.0 expresses it as a (possibly nested) diff.
+
+use std::collections::HashMap;
+
+use mongodb::bson::{Bson, Document};
+
+/// An update applied to a document, decoded from either MongoDB's `$v:1` modifier encoding or
+/// its `$v:2` diff encoding.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub enum UpdateSpec {
+    /// A `$v:1` style update expressed as `$set`/`$unset` modifiers.
+    Modifiers {
+        /// Fields to set (or overwrite) to a new value.
+        set: Document,
+        /// Fields to remove.
+        unset: Vec<String>,
+    },
+    /// A `$v:2` style diff.
+    Diff(Diff),
+    /// The update document didn't match either known encoding; kept verbatim so callers can
+    /// still inspect it.
+    Unknown(Document),
+}
+
+/// A single level of a `$v:2` diff: fields to set, remove or insert at this level, plus diffs of
+/// any nested sub-documents.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct Diff {
+    /// Fields to set to a new value (`u`).
+    pub set: Document,
+    /// Fields to remove (`d`).
+    pub unset: Vec<String>,
+    /// Fields to insert (`i`).
+    pub insert: Document,
+    /// Diffs of nested sub-documents, keyed by field name (the `s` prefix is stripped).
+    pub sub_diffs: HashMap<String, Diff>,
+}
+
+impl UpdateSpec {
+    /// Parse an update's `o` document into a structured `UpdateSpec`.
+    pub(crate) fn parse(o: &Document) -> UpdateSpec {
+        let is_v2 = matches!(o.get("$v"), Some(Bson::Int32(2)) | Some(Bson::Int64(2)));
+
+        if is_v2 {
+            if let Ok(diff) = o.get_document("diff") {
+                return UpdateSpec::Diff(Diff::parse(diff));
+            }
+        }
+
+        if o.contains_key("$set") || o.contains_key("$unset") {
+            return UpdateSpec::Modifiers {
+                set: o.get_document("$set").ok().cloned().unwrap_or_default(),
+                unset: o
+                    .get_document("$unset")
+                    .ok()
+                    .map(|u| u.keys().cloned().collect())
+                    .unwrap_or_default(),
+            };
+        }
+
+        UpdateSpec::Unknown(o.clone())
+    }
+
+    /// Mutate `base` (a pre-image document, typically fetched or cached by the caller) in place
+    /// into the post-image this update produces, so a full `T` can be deserialized from it.
+    pub fn apply(&self, base: &mut Document) {
+        match self {
+            UpdateSpec::Modifiers { set, unset } => {
+                for (k, v) in set {
+                    set_path(base, k, v.clone());
+                }
+                for k in unset {
+                    unset_path(base, k);
+                }
+            }
+            UpdateSpec::Diff(diff) => diff.apply(base),
+            UpdateSpec::Unknown(_) => {}
+        }
+    }
+}
+
+/// Set `path` (a `$v:1` modifier key, possibly a MongoDB dotted path like `"data.foo"`) to `value`
+/// within `base`, walking into (and creating, if missing) any intermediate sub-documents rather
+/// than inserting a single field literally named `"data.foo"`.
+fn set_path(base: &mut Document, path: &str, value: Bson) {
+    match path.split_once('.') {
+        Some((head, tail)) => {
+            if !matches!(base.get(head), Some(Bson::Document(_))) {
+                base.insert(head.to_string(), Bson::Document(Document::new()));
+            }
+            if let Some(Bson::Document(sub)) = base.get_mut(head) {
+                set_path(sub, tail, value);
+            }
+        }
+        None => {
+            base.insert(path.to_string(), value);
+        }
+    }
+}
+
+/// Remove `path` (a `$v:1` modifier key, possibly a dotted path) from `base`, walking into any
+/// intermediate sub-documents it names rather than removing a single field literally named
+/// `"data.foo"`.
+fn unset_path(base: &mut Document, path: &str) {
+    match path.split_once('.') {
+        Some((head, tail)) => {
+            if let Some(Bson::Document(sub)) = base.get_mut(head) {
+                unset_path(sub, tail);
+            }
+        }
+        None => {
+            base.remove(path);
+        }
+    }
+}
+
+impl Diff {
+    fn parse(diff: &Document) -> Diff {
+        let mut parsed = Diff {
+            set: diff.get_document("u").ok().cloned().unwrap_or_default(),
+            insert: diff.get_document("i").ok().cloned().unwrap_or_default(),
+            unset: diff
+                .get_document("d")
+                .ok()
+                .map(|d| d.keys().cloned().collect())
+                .unwrap_or_default(),
+            sub_diffs: HashMap::new(),
+        };
+
+        for (key, value) in diff {
+            if let (Some(field), Bson::Document(sub)) = (key.strip_prefix('s'), value) {
+                parsed.sub_diffs.insert(field.to_string(), Diff::parse(sub));
+            }
+        }
+
+        parsed
+    }
+
+    /// Apply this diff (and any nested sub-diffs) to `base` in place.
+    ///
+    /// Sub-array diffs use a different wire format (positional edits plus a length-truncation
+    /// marker) that isn't decoded here, so a `sub_diffs` entry for an array field is a no-op
+    /// rather than a best-effort guess.
+    fn apply(&self, base: &mut Document) {
+        for (k, v) in &self.set {
+            base.insert(k.clone(), v.clone());
+        }
+        for k in &self.unset {
+            base.remove(k);
+        }
+        for (k, v) in &self.insert {
+            base.insert(k.clone(), v.clone());
+        }
+        for (field, sub_diff) in &self.sub_diffs {
+            if let Some(Bson::Document(sub)) = base.get_mut(field) {
+                sub_diff.apply(sub);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diff, UpdateSpec};
+    use mongodb::bson::doc;
+    use std::collections::HashMap;
+
+    #[test]
+    fn update_spec_parses_v1_modifiers() {
+        let o = doc! {
+            "$set": { "foo": "bar" },
+            "$unset": { "baz": "" }
+        };
+
+        assert_eq!(
+            UpdateSpec::parse(&o),
+            UpdateSpec::Modifiers {
+                set: doc! { "foo": "bar" },
+                unset: vec!["baz".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn update_spec_parses_v2_diff() {
+        let o = doc! {
+            "$v": 2,
+            "diff": {
+                "u": { "foo": "bar" },
+                "d": { "baz": false },
+                "i": { "qux": 1 },
+            }
+        };
+
+        assert_eq!(
+            UpdateSpec::parse(&o),
+            UpdateSpec::Diff(Diff {
+                set: doc! { "foo": "bar" },
+                unset: vec!["baz".into()],
+                insert: doc! { "qux": 1 },
+                sub_diffs: HashMap::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn update_spec_parses_nested_v2_diff() {
+        let o = doc! {
+            "$v": 2,
+            "diff": {
+                "u": { "top": 1 },
+                "sdata": {
+                    "u": { "nested": 2 }
+                }
+            }
+        };
+
+        let mut nested = HashMap::new();
+        nested.insert(
+            "data".to_string(),
+            Diff {
+                set: doc! { "nested": 2 },
+                unset: vec![],
+                insert: doc! {},
+                sub_diffs: HashMap::new(),
+            },
+        );
+
+        assert_eq!(
+            UpdateSpec::parse(&o),
+            UpdateSpec::Diff(Diff {
+                set: doc! { "top": 1 },
+                unset: vec![],
+                insert: doc! {},
+                sub_diffs: nested,
+            })
+        );
+    }
+
+    #[test]
+    fn update_spec_falls_back_to_unknown() {
+        let o = doc! { "foo": "bar" };
+
+        assert_eq!(UpdateSpec::parse(&o), UpdateSpec::Unknown(doc! { "foo": "bar" }));
+    }
+
+    #[test]
+    fn update_spec_applies_v1_modifiers_to_a_pre_image() {
+        let spec = UpdateSpec::Modifiers {
+            set: doc! { "foo": "bar" },
+            unset: vec!["baz".into()],
+        };
+        let mut base = doc! { "foo": "old", "baz": 1, "keep": true };
+
+        spec.apply(&mut base);
+
+        assert_eq!(base, doc! { "foo": "bar", "keep": true });
+    }
+
+    #[test]
+    fn update_spec_applies_v1_modifiers_to_a_dotted_path() {
+        let spec = UpdateSpec::Modifiers {
+            set: doc! { "data.foo": "baz" },
+            unset: vec!["data.stale".into()],
+        };
+        let mut base = doc! { "data": { "foo": "old", "stale": true, "keep": 1 } };
+
+        spec.apply(&mut base);
+
+        assert_eq!(base, doc! { "data": { "foo": "baz", "keep": 1 } });
+    }
+
+    #[test]
+    fn update_spec_set_path_creates_missing_intermediate_documents() {
+        let spec = UpdateSpec::Modifiers {
+            set: doc! { "data.foo": "baz" },
+            unset: vec![],
+        };
+        let mut base = doc! {};
+
+        spec.apply(&mut base);
+
+        assert_eq!(base, doc! { "data": { "foo": "baz" } });
+    }
+
+    #[test]
+    fn update_spec_applies_v2_diff_to_a_pre_image() {
+        let mut nested = HashMap::new();
+        nested.insert(
+            "data".to_string(),
+            Diff {
+                set: doc! { "nested": 2 },
+                unset: vec!["stale".into()],
+                insert: doc! {},
+                sub_diffs: HashMap::new(),
+            },
+        );
+        let spec = UpdateSpec::Diff(Diff {
+            set: doc! { "top": 1 },
+            unset: vec!["gone".into()],
+            insert: doc! { "added": true },
+            sub_diffs: nested,
+        });
+        let mut base = doc! {
+            "top": "old",
+            "gone": true,
+            "data": { "nested": "old", "stale": true },
+        };
+
+        spec.apply(&mut base);
+
+        assert_eq!(
+            base,
+            doc! {
+                "top": 1,
+                "data": { "nested": 2 },
+                "added": true,
+            }
+        );
+    }
+}